@@ -1,10 +1,62 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 use crate::bbox::BBox;
 use gdal::{errors::GdalError, raster::ResampleAlg, Dataset};
 use itertools::Itertools;
+use rayon::{prelude::*, ThreadPool};
 use thiserror::Error;
 
+thread_local! {
+    // Each rayon worker thread gets its own read-only handle per raster path,
+    // since a single `Dataset` isn't `Sync` and can't be shared across the
+    // concurrent band reads below. This is deliberately separate from
+    // `request_handler::THREAD_LOCAL_DATA`, which caches the handles used by
+    // the tokio blocking pool to drive the (sequential, per-request)
+    // compositing loop: the two pools have different thread counts and
+    // lifetimes, and sharing one cache between them would mean a handle
+    // opened for a blocking-pool thread could be looked up, and closed, from
+    // a rayon worker thread that never opened it.
+    static RAYON_DATASETS: RefCell<HashMap<PathBuf, Dataset>> = RefCell::new(HashMap::new());
+}
+
+/// The rayon pool that runs the per-band reads below. Sized explicitly to
+/// match the tokio blocking pool (see `main::dataset_runtime`) instead of
+/// relying on rayon's default global pool, so the number of per-path
+/// `Dataset` handles cached in `RAYON_DATASETS` stays bounded to one per
+/// raster path per physical thread, regardless of how many raster layers a
+/// request composites.
+fn band_read_pool() -> &'static ThreadPool {
+    static POOL: OnceLock<ThreadPool> = OnceLock::new();
+
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(std::thread::available_parallelism().map_or(1, Into::into))
+            .build()
+            .expect("failed to build band-read thread pool")
+    })
+}
+
+fn with_thread_dataset<T>(
+    path: &Path,
+    f: impl FnOnce(&Dataset) -> Result<T, ReadError>,
+) -> Result<T, ReadError> {
+    RAYON_DATASETS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+
+        if !cache.contains_key(path) {
+            cache.insert(path.to_path_buf(), Dataset::open(path)?);
+        }
+
+        f(cache.get(path).expect("dataset was just inserted"))
+    })
+}
+
 pub enum Background {
     Alpha,
     Rgb(u8, u8, u8),
@@ -32,6 +84,50 @@ impl TryFrom<Cow<'_, str>> for Background {
     }
 }
 
+#[derive(Clone, Copy)]
+pub enum Resample {
+    Nearest,
+    Bilinear,
+    Cubic,
+    CubicSpline,
+    Lanczos,
+    Average,
+    Mode,
+}
+
+pub struct ResampleError();
+
+impl TryFrom<Cow<'_, str>> for Resample {
+    type Error = ResampleError;
+
+    fn try_from(value: Cow<'_, str>) -> Result<Self, Self::Error> {
+        match value.as_ref() {
+            "nearest" => Ok(Self::Nearest),
+            "bilinear" => Ok(Self::Bilinear),
+            "cubic" => Ok(Self::Cubic),
+            "cubicspline" => Ok(Self::CubicSpline),
+            "lanczos" => Ok(Self::Lanczos),
+            "average" => Ok(Self::Average),
+            "mode" => Ok(Self::Mode),
+            _ => Err(ResampleError()),
+        }
+    }
+}
+
+impl From<Resample> for ResampleAlg {
+    fn from(value: Resample) -> Self {
+        match value {
+            Resample::Nearest => Self::NearestNeighbour,
+            Resample::Bilinear => Self::Bilinear,
+            Resample::Cubic => Self::Cubic,
+            Resample::CubicSpline => Self::CubicSpline,
+            Resample::Lanczos => Self::Lanczos,
+            Resample::Average => Self::Average,
+            Resample::Mode => Self::Mode,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ReadError {
     #[error("band count error")]
@@ -40,14 +136,43 @@ pub enum ReadError {
     GdalError(#[from] GdalError),
 }
 
+/// Parameters for the single-band DEM hillshade rendering mode, following
+/// the usual `gdaldem hillshade` conventions (compass-degree azimuth,
+/// degrees above the horizon for altitude).
+#[derive(Clone, Copy)]
+pub struct HillshadeParams {
+    pub azimuth: f64,
+    pub altitude: f64,
+    pub z_factor: f64,
+}
+
+impl Default for HillshadeParams {
+    fn default() -> Self {
+        Self {
+            azimuth: 315.0,
+            altitude: 45.0,
+            z_factor: 1.0,
+        }
+    }
+}
+
 pub fn read_rgba_from_gdal(
     dataset: &Dataset,
+    path: &Path,
     result_bbox: BBox<f64>,
     size: (usize, usize),
     background: Background,
+    resample: Resample,
+    hillshade: HillshadeParams,
 ) -> Result<(bool, Vec<u8>), ReadError> {
+    let resample_alg = ResampleAlg::from(resample);
+
     let input_count = dataset.raster_count();
 
+    if input_count == 1 {
+        return read_hillshade_from_gdal(dataset, result_bbox, size, resample_alg, hillshade);
+    }
+
     if !matches!(input_count, 3..=4) {
         return Err(ReadError::BandCountError);
     }
@@ -74,14 +199,27 @@ pub fn read_rgba_from_gdal(
 
     let band_size = size.0 * size.1;
 
-    // TODO consider mask
+    let has_native_alpha = input_count == 4;
 
-    let result_count = if input_count == 4 && matches!(background, Background::Alpha) {
+    // `Background::Alpha` means the caller wants transparency in the
+    // output (overlays always request it; a base layer can too). A
+    // 3-band dataset has no literal alpha band to carry that, so its
+    // per-pixel transparency is synthesized from the RGB bands' mask
+    // instead: fully opaque where all three are valid, fully transparent
+    // where any of them is masked out.
+    let result_count = if matches!(background, Background::Alpha) {
         4
     } else {
         3
     };
 
+    let synthesize_alpha = result_count == 4 && !has_native_alpha;
+
+    // Only the bands that actually exist on the dataset are read: a
+    // native alpha band (index 3) is read alongside R/G/B when present,
+    // but never fabricated when `synthesize_alpha` is doing that instead.
+    let read_count = if has_native_alpha { result_count } else { 3 };
+
     let (raster_width, raster_height) = dataset.raster_size();
 
     // Adjust the window to fit within the raster bounds
@@ -121,8 +259,6 @@ pub fn read_rgba_from_gdal(
         size.1 - hh
     };
 
-    let mut source_band = vec![0u8; hh * ww];
-
     let mut result_data = match (background, result_count) {
         (Background::Rgb(r, g, b), 4) => vec![r, g, b, 255]
             .into_iter()
@@ -139,48 +275,73 @@ pub fn read_rgba_from_gdal(
         _ => vec![0u8; band_size * result_count],
     };
 
-    let alpha_band = if result_count == 4 {
-        Some({
-            let mut source_band = vec![0u8; hh * ww];
-
-            dataset.rasterband(4)?.read_into_slice::<u8>(
-                window,
-                window_size,
-                desired_size,
-                &mut source_band,
-                Some(ResampleAlg::NearestNeighbour),
-            )?;
-
-            source_band
-        })
-    } else {
-        None
-    };
+    // Every read below targets the same `window`/`window_size`/`desired_size`
+    // geometry, so the resulting buffers stay pixel-aligned regardless of
+    // which order they finish in.
+    #[derive(Clone, Copy)]
+    enum BandRead {
+        Alpha,
+        Band(usize),
+        Mask(usize),
+    }
 
-    for band_index in 0..result_count {
-        let band = dataset.rasterband(band_index + 1)?;
+    let mut reads = Vec::with_capacity(1 + read_count * 2);
 
-        // band.mask_flags()?.
+    if has_native_alpha {
+        reads.push(BandRead::Alpha);
+    }
 
-        let mask_band = band.open_mask_band()?;
+    for band_index in 0..read_count {
+        reads.push(BandRead::Band(band_index));
+        reads.push(BandRead::Mask(band_index));
+    }
 
-        let mut mask_data = vec![0u8; hh * ww];
+    let mut alpha_band: Option<Vec<u8>> = None;
+    let mut band_data: Vec<Option<Vec<u8>>> = vec![None; read_count];
+    let mut mask_data: Vec<Option<Vec<u8>>> = vec![None; read_count];
+
+    let read_results = band_read_pool().install(|| {
+        reads
+            .into_par_iter()
+            .map(|read| -> Result<(BandRead, Vec<u8>), ReadError> {
+                let buf = with_thread_dataset(path, |dataset| -> Result<Vec<u8>, ReadError> {
+                    let mut buf = vec![0u8; hh * ww];
+
+                    let band = match read {
+                        BandRead::Alpha => dataset.rasterband(4)?,
+                        BandRead::Band(band_index) => dataset.rasterband(band_index + 1)?,
+                        BandRead::Mask(band_index) => {
+                            dataset.rasterband(band_index + 1)?.open_mask_band()?
+                        }
+                    };
+
+                    band.read_into_slice::<u8>(
+                        window,
+                        window_size,
+                        desired_size,
+                        &mut buf,
+                        Some(resample_alg),
+                    )?;
+
+                    Ok(buf)
+                })?;
+
+                Ok((read, buf))
+            })
+            .collect::<Result<Vec<_>, ReadError>>()
+    })?;
+
+    for (read, buf) in read_results {
+        match read {
+            BandRead::Alpha => alpha_band = Some(buf),
+            BandRead::Band(band_index) => band_data[band_index] = Some(buf),
+            BandRead::Mask(band_index) => mask_data[band_index] = Some(buf),
+        }
+    }
 
-        mask_band.read_into_slice::<u8>(
-            window,
-            window_size,
-            desired_size,
-            &mut mask_data,
-            Some(ResampleAlg::NearestNeighbour),
-        )?;
-
-        band.read_into_slice::<u8>(
-            window,
-            window_size,
-            desired_size,
-            &mut source_band,
-            Some(ResampleAlg::NearestNeighbour),
-        )?;
+    for band_index in 0..read_count {
+        let band_data = band_data[band_index].as_ref().expect("band data was read");
+        let mask_data = mask_data[band_index].as_ref().expect("mask data was read");
 
         for y in 0..size.0.min(hh) {
             for x in 0..size.1.min(ww) {
@@ -191,11 +352,11 @@ pub fn read_rgba_from_gdal(
                         ((y + off_y) * size.0 + (x + off_x)) * result_count + band_index;
 
                     result_data[result_index] = alpha_band.as_ref().map_or_else(
-                        || source_band[data_index],
+                        || band_data[data_index],
                         |alpha_band| {
                             let alpha = u16::from(alpha_band[data_index]);
 
-                            ((u16::from(source_band[data_index]) * alpha
+                            ((u16::from(band_data[data_index]) * alpha
                                 + u16::from(result_data[result_index]) * (255 - alpha))
                                 / 255) as u8
                         },
@@ -205,6 +366,24 @@ pub fn read_rgba_from_gdal(
         }
     }
 
+    if synthesize_alpha {
+        for y in 0..size.0.min(hh) {
+            for x in 0..size.1.min(ww) {
+                let data_index = y * ww + x;
+
+                let all_valid = (0..read_count).all(|band_index| {
+                    mask_data[band_index].as_ref().expect("mask data was read")[data_index] != 0
+                });
+
+                if all_valid {
+                    let result_index = ((y + off_y) * size.0 + (x + off_x)) * result_count + 3;
+
+                    result_data[result_index] = 255;
+                }
+            }
+        }
+    }
+
     // premultiply
     if result_count == 4 {
         for i in (0..result_data.len()).step_by(3) {
@@ -222,3 +401,486 @@ pub fn read_rgba_from_gdal(
 
     Ok((result_count == 4, result_data))
 }
+
+/// Shades a single-band `Float32` DEM into an RGBA tile (grey shade, fully
+/// opaque; nodata pixels fully transparent) using Horn's 3x3
+/// method, the same slope/aspect formula `gdaldem hillshade` uses. A
+/// one-pixel halo is read around the requested window so the 3x3 kernel
+/// stays valid at the tile's edge and adjacent tiles shade seamlessly.
+fn read_hillshade_from_gdal(
+    dataset: &Dataset,
+    result_bbox: BBox<f64>,
+    size: (usize, usize),
+    resample_alg: ResampleAlg,
+    hillshade: HillshadeParams,
+) -> Result<(bool, Vec<u8>), ReadError> {
+    let [gt_x_off, gt_x_width, _, gt_y_off, _, gt_y_width] = dataset.geo_transform()?;
+
+    let cellsize = gt_x_width.abs();
+
+    let BBox {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+    } = result_bbox;
+
+    let pixel_width = (max_x - min_x) / size.0 as f64;
+    let pixel_height = (max_y - min_y) / size.1 as f64;
+
+    let pixel_min_x = ((min_x - pixel_width - gt_x_off) / gt_x_width).round() as isize;
+    let pixel_max_x = ((max_x + pixel_width - gt_x_off) / gt_x_width).round() as isize;
+    let pixel_max_y = ((min_y - pixel_height - gt_y_off) / gt_y_width).round() as isize;
+    let pixel_min_y = ((max_y + pixel_height - gt_y_off) / gt_y_width).round() as isize;
+
+    let window_x = pixel_min_x;
+    let window_y = pixel_min_y;
+    let source_width = pixel_max_x - pixel_min_x;
+    let source_height = pixel_max_y - pixel_min_y;
+
+    let (raster_width, raster_height) = dataset.raster_size();
+
+    // Adjust the window to fit within the raster bounds, same as read_rgba_from_gdal.
+    let adj_window_x = window_x.max(0).min(raster_width as isize);
+    let adj_window_y = window_y.max(0).min(raster_height as isize);
+
+    let adj_source_width =
+        ((window_x + source_width).min(raster_width as isize) - adj_window_x).max(0) as usize;
+    let adj_source_height =
+        ((window_y + source_height).min(raster_height as isize) - adj_window_y).max(0) as usize;
+
+    let halo_width = size.0 + 2;
+    let halo_height = size.1 + 2;
+
+    let ww = (halo_width as f64 * (adj_source_width as f64 / source_width as f64)) as usize;
+    let hh = (halo_height as f64 * (adj_source_height as f64 / source_height as f64)) as usize;
+
+    let window = (adj_window_x, adj_window_y);
+    let window_size = (adj_source_width, adj_source_height);
+    let desired_size = (ww, hh);
+
+    let off_x = if window_x == adj_window_x {
+        0
+    } else if pixel_min_x <= 0 && pixel_max_x >= raster_width as isize {
+        (0.0_f64 - halo_width as f64 * window_x as f64 / source_width as f64) as usize
+    } else {
+        halo_width - ww
+    };
+
+    let off_y = if window_y == adj_window_y {
+        0
+    } else if pixel_min_y <= 0 && pixel_max_y >= raster_height as isize {
+        (0.0 - halo_height as f64 * window_y as f64 / source_height as f64) as usize
+    } else {
+        halo_height - hh
+    };
+
+    // Pixels outside the clamped window (i.e. past the raster edge) stay at
+    // elevation 0 / mask 0. The shading loop below leaves such a pixel
+    // transparent when it is the centre pixel, same as a real masked-out DEM
+    // pixel; as a neighbour it enters the 3x3 kernel with elevation 0.
+    let mut elevation = vec![0f32; halo_width * halo_height];
+    let mut mask = vec![0u8; halo_width * halo_height];
+
+    if ww > 0 && hh > 0 {
+        let band = dataset.rasterband(1)?;
+
+        let mut elevation_window = vec![0f32; ww * hh];
+
+        band.read_into_slice::<f32>(
+            window,
+            window_size,
+            desired_size,
+            &mut elevation_window,
+            Some(resample_alg),
+        )?;
+
+        let mut mask_window = vec![0u8; ww * hh];
+
+        band.open_mask_band()?.read_into_slice::<u8>(
+            window,
+            window_size,
+            desired_size,
+            &mut mask_window,
+            Some(resample_alg),
+        )?;
+
+        for y in 0..hh {
+            for x in 0..ww {
+                let dst_index = (y + off_y) * halo_width + (x + off_x);
+                let src_index = y * ww + x;
+
+                elevation[dst_index] = elevation_window[src_index];
+                mask[dst_index] = mask_window[src_index];
+            }
+        }
+    }
+
+    let zenith = (90.0 - hillshade.altitude).to_radians();
+    let azimuth = (360.0 - hillshade.azimuth + 90.0).rem_euclid(360.0).to_radians();
+
+    let mut result_data = vec![0u8; size.0 * size.1 * 4];
+
+    for y in 0..size.1 {
+        for x in 0..size.0 {
+            let halo_index = |dx: isize, dy: isize| {
+                let xi = (x as isize + 1 + dx) as usize;
+                let yi = (y as isize + 1 + dy) as usize;
+                yi * halo_width + xi
+            };
+
+            let index = (y * size.0 + x) * 4;
+
+            if mask[halo_index(0, 0)] == 0 {
+                // Leave nodata pixels fully transparent instead of shading them.
+                continue;
+            }
+
+            let at = |dx: isize, dy: isize| f64::from(elevation[halo_index(dx, dy)]);
+
+            let a = at(-1, -1);
+            let b = at(0, -1);
+            let c = at(1, -1);
+            let d = at(-1, 0);
+            let f = at(1, 0);
+            let g = at(-1, 1);
+            let h = at(0, 1);
+            let i = at(1, 1);
+
+            let dz_dx = ((c + 2.0 * f + i) - (a + 2.0 * d + g)) / (8.0 * cellsize);
+            let dz_dy = ((g + 2.0 * h + i) - (a + 2.0 * b + c)) / (8.0 * cellsize);
+
+            let slope = (hillshade.z_factor * (dz_dx * dz_dx + dz_dy * dz_dy).sqrt()).atan();
+            let aspect = dz_dy.atan2(-dz_dx);
+
+            let illumination = 255.0
+                * (zenith.cos() * slope.cos()
+                    + zenith.sin() * slope.sin() * (azimuth - aspect).cos());
+
+            let illumination = illumination.clamp(0.0, 255.0) as u8;
+
+            result_data[index] = illumination;
+            result_data[index + 1] = illumination;
+            result_data[index + 2] = illumination;
+            result_data[index + 3] = 255;
+        }
+    }
+
+    Ok((true, result_data))
+}
+
+/// Alpha-blends `overlay` over `base` in place, using the same
+/// premultiplied-alpha formula as the per-band compositing above. `opacity`
+/// further scales the overlay's own alpha so callers can tune blend
+/// strength per layer. `base` keeps its own channel count (3 or 4); a
+/// 3-channel overlay is treated as fully opaque.
+pub fn composite_over(
+    base: &mut [u8],
+    base_has_alpha: bool,
+    overlay: &[u8],
+    overlay_has_alpha: bool,
+    opacity: f32,
+) {
+    let base_channels = if base_has_alpha { 4 } else { 3 };
+    let overlay_channels = if overlay_has_alpha { 4 } else { 3 };
+
+    let pixel_count = base.len() / base_channels;
+
+    for pixel in 0..pixel_count {
+        let base_index = pixel * base_channels;
+        let overlay_index = pixel * overlay_channels;
+
+        let overlay_alpha = if overlay_has_alpha {
+            f32::from(overlay[overlay_index + 3]) / 255.0
+        } else {
+            1.0
+        } * opacity;
+
+        let inv_alpha = 1.0 - overlay_alpha;
+
+        for c in 0..3 {
+            base[base_index + c] = (f32::from(overlay[overlay_index + c]) * opacity
+                + f32::from(base[base_index + c]) * inv_alpha)
+                as u8;
+        }
+
+        if base_has_alpha {
+            base[base_index + 3] =
+                (overlay_alpha * 255.0 + f32::from(base[base_index + 3]) * inv_alpha) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gdal::{raster::Buffer, DriverManager};
+    use std::fs;
+
+    /// The per-band read loop `read_rgba_from_gdal` used before it was
+    /// parallelized with rayon. Kept only so the test below can check the
+    /// rayon version still produces byte-identical output.
+    fn read_rgba_sequential(
+        dataset: &Dataset,
+        result_bbox: BBox<f64>,
+        size: (usize, usize),
+        background: Background,
+        resample: Resample,
+    ) -> Result<(bool, Vec<u8>), ReadError> {
+        let resample_alg = ResampleAlg::from(resample);
+
+        let input_count = dataset.raster_count();
+
+        let [gt_x_off, gt_x_width, _, gt_y_off, _, gt_y_width] = dataset.geo_transform()?;
+
+        let BBox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        } = result_bbox;
+
+        let pixel_min_x = ((min_x - gt_x_off) / gt_x_width).round() as isize;
+        let pixel_max_x = ((max_x - gt_x_off) / gt_x_width).round() as isize;
+        let pixel_max_y = ((min_y - gt_y_off) / gt_y_width).round() as isize;
+        let pixel_min_y = ((max_y - gt_y_off) / gt_y_width).round() as isize;
+
+        let window_x = pixel_min_x;
+        let window_y = pixel_min_y;
+        let source_width = pixel_max_x - pixel_min_x;
+        let source_height = pixel_max_y - pixel_min_y;
+
+        let band_size = size.0 * size.1;
+
+        let has_native_alpha = input_count == 4;
+
+        let result_count = if matches!(background, Background::Alpha) {
+            4
+        } else {
+            3
+        };
+
+        let synthesize_alpha = result_count == 4 && !has_native_alpha;
+
+        let read_count = if has_native_alpha { result_count } else { 3 };
+
+        let (raster_width, raster_height) = dataset.raster_size();
+
+        let adj_window_x = window_x.max(0).min(raster_width as isize);
+        let adj_window_y = window_y.max(0).min(raster_height as isize);
+
+        let adj_source_width: usize =
+            ((window_x + source_width).min(raster_width as isize) - adj_window_x).max(0) as usize;
+        let adj_source_height =
+            ((window_y + source_height).min(raster_height as isize) - adj_window_y).max(0) as usize;
+
+        let ww = (size.0 as f64 * (adj_source_width as f64 / source_width as f64)) as usize;
+        let hh = (size.1 as f64 * (adj_source_height as f64 / source_height as f64)) as usize;
+
+        let window = (adj_window_x, adj_window_y);
+        let window_size = (adj_source_width, adj_source_height);
+        let desired_size = (ww, hh);
+
+        let off_x = if window_x == adj_window_x {
+            0
+        } else if pixel_min_x <= 0 && pixel_max_x >= raster_width as isize {
+            (0.0_f64 - size.0 as f64 * window_x as f64 / source_width as f64) as usize
+        } else {
+            size.0 - ww
+        };
+
+        let off_y = if window_y == adj_window_y {
+            0
+        } else if pixel_min_y <= 0 && pixel_max_y >= raster_height as isize {
+            (0.0 - size.1 as f64 * window_y as f64 / source_height as f64) as usize
+        } else {
+            size.1 - hh
+        };
+
+        let mut result_data = match (background, result_count) {
+            (Background::Rgb(r, g, b), 4) => vec![r, g, b, 255]
+                .into_iter()
+                .cycle()
+                .take(band_size * result_count)
+                .collect::<Vec<u8>>(),
+
+            (Background::Rgb(r, g, b), 3) => vec![r, g, b]
+                .into_iter()
+                .cycle()
+                .take(band_size * result_count)
+                .collect::<Vec<u8>>(),
+
+            _ => vec![0u8; band_size * result_count],
+        };
+
+        let alpha_band = if has_native_alpha {
+            let mut buf = vec![0u8; hh * ww];
+
+            dataset.rasterband(4)?.read_into_slice::<u8>(
+                window,
+                window_size,
+                desired_size,
+                &mut buf,
+                Some(resample_alg),
+            )?;
+
+            Some(buf)
+        } else {
+            None
+        };
+
+        let mut all_mask_data: Vec<Vec<u8>> = Vec::with_capacity(read_count);
+
+        for band_index in 0..read_count {
+            let band = dataset.rasterband(band_index + 1)?;
+
+            let mut mask_data = vec![0u8; hh * ww];
+
+            band.open_mask_band()?.read_into_slice::<u8>(
+                window,
+                window_size,
+                desired_size,
+                &mut mask_data,
+                Some(resample_alg),
+            )?;
+
+            let mut band_data = vec![0u8; hh * ww];
+
+            band.read_into_slice::<u8>(
+                window,
+                window_size,
+                desired_size,
+                &mut band_data,
+                Some(resample_alg),
+            )?;
+
+            for y in 0..size.0.min(hh) {
+                for x in 0..size.1.min(ww) {
+                    let data_index = y * ww + x;
+
+                    if mask_data[data_index] != 0 {
+                        let result_index =
+                            ((y + off_y) * size.0 + (x + off_x)) * result_count + band_index;
+
+                        result_data[result_index] = alpha_band.as_ref().map_or_else(
+                            || band_data[data_index],
+                            |alpha_band| {
+                                let alpha = u16::from(alpha_band[data_index]);
+
+                                ((u16::from(band_data[data_index]) * alpha
+                                    + u16::from(result_data[result_index]) * (255 - alpha))
+                                    / 255) as u8
+                            },
+                        );
+                    }
+                }
+            }
+
+            all_mask_data.push(mask_data);
+        }
+
+        if synthesize_alpha {
+            for y in 0..size.0.min(hh) {
+                for x in 0..size.1.min(ww) {
+                    let data_index = y * ww + x;
+
+                    let all_valid = all_mask_data
+                        .iter()
+                        .all(|mask_data| mask_data[data_index] != 0);
+
+                    if all_valid {
+                        let result_index = ((y + off_y) * size.0 + (x + off_x)) * result_count + 3;
+
+                        result_data[result_index] = 255;
+                    }
+                }
+            }
+        }
+
+        if result_count == 4 {
+            for i in (0..result_data.len()).step_by(3) {
+                let alpha = f32::from(result_data[i + 3]) / 255.0;
+
+                let r = (f32::from(result_data[i]) * alpha) as u8;
+                let g = (f32::from(result_data[i + 1]) * alpha) as u8;
+                let b = (f32::from(result_data[i + 2]) * alpha) as u8;
+
+                result_data[i] = r;
+                result_data[i + 1] = g;
+                result_data[i + 2] = b;
+            }
+        }
+
+        Ok((result_count == 4, result_data))
+    }
+
+    fn make_test_dataset(path: &Path) {
+        let driver = DriverManager::get_driver_by_name("GTiff").expect("GTiff driver available");
+
+        let mut dataset = driver
+            .create_with_band_type::<u8, _>(path, 32, 32, 4)
+            .expect("create test raster");
+
+        dataset
+            .set_geo_transform(&[0.0, 1.0, 0.0, 32.0, 0.0, -1.0])
+            .expect("set geo transform");
+
+        for band_index in 1..=4 {
+            let mut band = dataset.rasterband(band_index).expect("rasterband");
+
+            let data: Vec<u8> = (0..32 * 32)
+                .map(|i| ((i * band_index + band_index) % 256) as u8)
+                .collect();
+
+            band.write((0, 0), (32, 32), &Buffer::new((32, 32), data))
+                .expect("write band data");
+        }
+    }
+
+    #[test]
+    fn parallel_reads_match_sequential() {
+        let path = std::env::temp_dir().join(format!(
+            "tileserver-parallel-reads-test-{}.tif",
+            std::process::id()
+        ));
+
+        make_test_dataset(&path);
+
+        let dataset = Dataset::open(&path).expect("reopen test raster");
+
+        // An off-center, partly out-of-bounds window so both the background
+        // fill and the edge-clamping paths are exercised, not just the
+        // straightforward fully-covered case.
+        let bbox = BBox {
+            min_x: -4.0,
+            min_y: 4.0,
+            max_x: 20.0,
+            max_y: 28.0,
+        };
+
+        let (parallel_has_alpha, parallel_data) = read_rgba_from_gdal(
+            &dataset,
+            &path,
+            bbox,
+            (16, 16),
+            Background::Rgb(10, 20, 30),
+            Resample::Bilinear,
+            HillshadeParams::default(),
+        )
+        .expect("parallel read");
+
+        let (sequential_has_alpha, sequential_data) = read_rgba_sequential(
+            &dataset,
+            bbox,
+            (16, 16),
+            Background::Rgb(10, 20, 30),
+            Resample::Bilinear,
+        )
+        .expect("sequential read");
+
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(parallel_has_alpha, sequential_has_alpha);
+        assert_eq!(parallel_data, sequential_data);
+    }
+}
@@ -9,12 +9,7 @@ use clap::Parser;
 use hyper::{server::conn::http1, service::service_fn};
 use hyper_util::rt::TokioIo;
 use request_handler::handle_request;
-use std::{
-    net::SocketAddr,
-    path::{Path, PathBuf},
-    sync::Arc,
-    thread,
-};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, thread};
 use tokio::net::TcpListener;
 
 #[derive(Parser, Debug)]
@@ -24,9 +19,13 @@ struct Args {
     #[arg(short, long)]
     socket_addr: Option<String>,
 
-    /// Raster file
-    #[arg(short, long)]
-    raster_file: PathBuf,
+    /// Raster file. Repeat to composite several layers, from bottom to top.
+    #[arg(short, long, required = true)]
+    raster_file: Vec<PathBuf>,
+
+    /// Maximum allowed value of the `size` query parameter.
+    #[arg(long, default_value_t = 2048)]
+    max_tile_size: u32,
 }
 
 #[tokio::main]
@@ -53,9 +52,9 @@ async fn main() -> Result<()> {
         |s| s.parse(),
     )?;
 
-    let raster_file: &Path = Box::leak(args.raster_file.into_boxed_path());
+    let max_tile_size = args.max_tile_size;
 
-    // let raster_file = Arc::new(&args.raster_file);
+    let raster_files: &'static [PathBuf] = Box::leak(args.raster_file.into_boxed_slice());
 
     let listener = TcpListener::bind(addr).await?;
 
@@ -69,7 +68,7 @@ async fn main() -> Result<()> {
         let sfn = service_fn(move |req| {
             let pool = pool.clone();
 
-            async move { handle_request(pool, req, raster_file).await }
+            async move { handle_request(pool, req, raster_files, max_tile_size).await }
         });
 
         tokio::spawn(async move {
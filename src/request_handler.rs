@@ -1,4 +1,6 @@
-use crate::gdal_reader::{read_rgba_from_gdal, Background, ReadError};
+use crate::gdal_reader::{
+    composite_over, read_rgba_from_gdal, Background, HillshadeParams, ReadError, Resample,
+};
 use crate::xyz::tile_bounds_to_epsg3857;
 use gdal::Dataset;
 use http_body_util::{combinators::BoxBody, BodyExt, Full};
@@ -7,22 +9,22 @@ use hyper::{
     Method, Request, Response, StatusCode,
 };
 use image::ImageError;
-use image::{codecs::jpeg::JpegEncoder, ImageEncoder};
+use image::{codecs::jpeg::JpegEncoder, codecs::png::PngEncoder, ImageEncoder};
 use std::convert::Infallible;
-use std::path::Path;
-use std::{cell::RefCell, io::Cursor, sync::Arc};
+use std::path::PathBuf;
+use std::{borrow::Cow, cell::RefCell, io::Cursor, sync::Arc};
 use tokio::runtime::Runtime;
 use tokio::task::JoinError;
 use url::Url;
 use webp::WebPEncodingError;
 
 thread_local! {
-    static THREAD_LOCAL_DATA: RefCell<Option<Dataset>> = const {RefCell::new(None)};
+    static THREAD_LOCAL_DATA: RefCell<Option<Vec<Dataset>>> = const {RefCell::new(None)};
 }
 
 enum ImageType {
     Jpeg,
-    // Png,
+    Png,
     Webp,
 }
 
@@ -32,12 +34,20 @@ impl TryFrom<&str> for ImageType {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "jpg" | "jpeg" => Ok(Self::Jpeg),
+            "png" => Ok(Self::Png),
             "webp" => Ok(Self::Webp),
             _ => Err(format!("unsupported extension {value}")),
         }
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+#[error("tile size must be between 1 and {max}, got {size}")]
+struct DimensionError {
+    size: u32,
+    max: u32,
+}
+
 #[derive(thiserror::Error, Debug)]
 enum ProcessingError {
     #[error("join error")]
@@ -65,7 +75,8 @@ pub enum BodyError {
 pub async fn handle_request(
     pool: Arc<Runtime>,
     req: Request<Incoming>,
-    raster_path: &'static Path,
+    raster_paths: &'static [PathBuf],
+    max_tile_size: u32,
 ) -> Result<Response<BoxBody<Bytes, BodyError>>, hyper::http::Error> {
     if req.method() != Method::GET {
         return http_error(StatusCode::METHOD_NOT_ALLOWED);
@@ -81,6 +92,12 @@ pub async fn handle_request(
 
     let mut background = Background::Alpha;
 
+    let mut resample = Resample::Bilinear;
+
+    let mut opacities: Vec<f32> = Vec::new();
+
+    let mut hillshade = HillshadeParams::default();
+
     for pair in url.query_pairs() {
         match pair.0.as_ref() {
             "background" | "bg" => {
@@ -89,6 +106,36 @@ pub async fn handle_request(
                     Err(_) => return http_error(StatusCode::BAD_REQUEST),
                 }
             }
+            "resample" | "r" => {
+                resample = match pair.1.try_into() {
+                    Ok(resample) => resample,
+                    Err(_) => return http_error(StatusCode::BAD_REQUEST),
+                }
+            }
+            "opacity" => {
+                opacities = match pair.1.split(',').map(str::parse::<f32>).collect() {
+                    Ok(opacities) => opacities,
+                    Err(_) => return http_error(StatusCode::BAD_REQUEST),
+                }
+            }
+            "azimuth" => {
+                hillshade.azimuth = match pair.1.parse() {
+                    Ok(azimuth) => azimuth,
+                    Err(_) => return http_error(StatusCode::BAD_REQUEST),
+                }
+            }
+            "altitude" => {
+                hillshade.altitude = match pair.1.parse() {
+                    Ok(altitude) => altitude,
+                    Err(_) => return http_error(StatusCode::BAD_REQUEST),
+                }
+            }
+            "z_factor" => {
+                hillshade.z_factor = match pair.1.parse() {
+                    Ok(z_factor) => z_factor,
+                    Err(_) => return http_error(StatusCode::BAD_REQUEST),
+                }
+            }
             "quality" | "q" => {
                 quality = match pair.1.parse::<f32>() {
                     Ok(quality) => quality,
@@ -105,6 +152,17 @@ pub async fn handle_request(
         }
     }
 
+    if size == 0 || size > max_tile_size {
+        return http_error_msg(
+            StatusCode::BAD_REQUEST,
+            &DimensionError {
+                size,
+                max: max_tile_size,
+            }
+            .to_string(),
+        );
+    }
+
     let parts: Vec<_> = path.splitn(2, '.').collect();
 
     let ext: Result<Option<ImageType>, _> = parts.get(1).map(|&x| x.try_into()).transpose();
@@ -136,11 +194,51 @@ pub async fn handle_request(
                     let mut data = data.borrow_mut();
 
                     let (has_alpha, raster) = {
-                        let ds = data.get_or_insert_with(|| {
-                            Dataset::open(raster_path).expect("error opening dataset")
+                        let datasets = data.get_or_insert_with(|| {
+                            raster_paths
+                                .iter()
+                                .map(|path| Dataset::open(path).expect("error opening dataset"))
+                                .collect()
                         });
 
-                        read_rgba_from_gdal(ds, bbox, (size as usize, size as usize), background)?
+                        let mut datasets = datasets.iter().zip(raster_paths.iter());
+
+                        let (base_ds, base_path) =
+                            datasets.next().expect("at least one raster file");
+
+                        let (has_alpha, mut raster) = read_rgba_from_gdal(
+                            base_ds,
+                            base_path,
+                            bbox,
+                            (size as usize, size as usize),
+                            background,
+                            resample,
+                            hillshade,
+                        )?;
+
+                        for (index, (overlay_ds, overlay_path)) in datasets.enumerate() {
+                            let (overlay_has_alpha, overlay) = read_rgba_from_gdal(
+                                overlay_ds,
+                                overlay_path,
+                                bbox,
+                                (size as usize, size as usize),
+                                Background::Alpha,
+                                resample,
+                                hillshade,
+                            )?;
+
+                            let opacity = opacities.get(index).copied().unwrap_or(1.0);
+
+                            composite_over(
+                                &mut raster,
+                                has_alpha,
+                                &overlay,
+                                overlay_has_alpha,
+                                opacity,
+                            );
+                        }
+
+                        (has_alpha, raster)
                     };
 
                     match ext {
@@ -162,11 +260,40 @@ pub async fn handle_request(
 
                             let cursor = Cursor::new(&mut img_data);
 
+                            // JPEG has no alpha channel; drop it rather than
+                            // handing the encoder a buffer wider than it expects.
+                            let rgb = if has_alpha {
+                                Cow::Owned(
+                                    raster
+                                        .chunks_exact(4)
+                                        .flat_map(|pixel| &pixel[..3])
+                                        .copied()
+                                        .collect::<Vec<u8>>(),
+                                )
+                            } else {
+                                Cow::Borrowed(&raster)
+                            };
+
                             JpegEncoder::new_with_quality(cursor, (quality * 2.55).round() as u8)
-                                .write_image(&raster, size, size, image::ExtendedColorType::Rgb8)?;
+                                .write_image(&rgb, size, size, image::ExtendedColorType::Rgb8)?;
 
                             Ok((ImageType::Jpeg, Bytes::from(img_data)))
                         }
+                        Some(ImageType::Png) => {
+                            let mut img_data = Vec::<u8>::new();
+
+                            let cursor = Cursor::new(&mut img_data);
+
+                            let color_type = if has_alpha {
+                                image::ExtendedColorType::Rgba8
+                            } else {
+                                image::ExtendedColorType::Rgb8
+                            };
+
+                            PngEncoder::new(cursor).write_image(&raster, size, size, color_type)?;
+
+                            Ok((ImageType::Png, Bytes::from(img_data)))
+                        }
                         None => Err(ProcessingError::HttpError(StatusCode::NOT_FOUND, None)),
                     }
                 })
@@ -191,6 +318,7 @@ pub async fn handle_request(
                             "Content-Type",
                             match message.0 {
                                 ImageType::Jpeg => "image/jpeg",
+                                ImageType::Png => "image/png",
                                 ImageType::Webp => "image/webp",
                             },
                         )